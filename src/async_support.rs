@@ -1,56 +1,538 @@
 //! Main Thread Async Support
-//! 
+//!
 //! This module provides async support for apps using swift-bridge as a static library.
 //! All async code runs on the main thread to ensure thread-safety with UI frameworks.
 //!
 //! Usage from the containing app:
-//! 1. Call `get_async_runtime()` once to get the runtime instance
+//! 1. Call `get_async_runtime()` once to get a handle for submitting work
 //! 2. Call `swift_bridge_update_runtime()` every frame (e.g. in CADisplayLink or timer)
-//! 
+//!
 //! The runtime processes tasks in this order:
-//! 1. Takes pending tasks from the queue
-//! 2. Executes them on the main thread
-//! 3. Yields to allow other work
+//! 1. Moves any newly queued same-thread tasks onto the `LocalSet`, FIFO
+//! 2. Drains tasks submitted from other threads via `AsyncRuntimeHandle`
+//! 3. Drives the `LocalSet` in cooperative steps up to the caller's time budget
+//! 4. Yields back to the caller (the frame loop) rather than running to completion
 
+use std::any::Any;
+use std::collections::VecDeque;
 use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::pin::Pin;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
+use tokio::task::LocalSet;
 
 thread_local! {
-    static ASYNC_RUNTIME: RefCell<Option<Runtime>> = RefCell::new(None);
-    static TASKS: RefCell<Vec<AsyncFnToSpawn>> = RefCell::new(Vec::new());
+    static ASYNC_RUNTIME: RefCell<Option<(Runtime, LocalSet)>> = const { RefCell::new(None) };
+    // A queue (not a stack) so tasks are handed to the `LocalSet` in the order they
+    // were submitted; otherwise a burst of work could starve early-submitted tasks.
+    static TASKS: RefCell<VecDeque<AsyncFnToSpawn>> = const { RefCell::new(VecDeque::new()) };
+    static CROSS_THREAD_CHANNEL: RefCell<Option<(mpsc::Sender<CrossThreadTask>, mpsc::Receiver<CrossThreadTask>)>> =
+        const { RefCell::new(None) };
+    static PANIC_HANDLER: RefCell<Option<SwiftCallbackWrapper>> = const { RefCell::new(None) };
+    // Count of tasks handed to the `LocalSet` that haven't finished yet, regardless
+    // of whether they arrived via `spawn_task` or an `AsyncRuntimeHandle`. Lets
+    // `swift_bridge_update_runtime`'s budget loop notice it has no more tasks at all
+    // to drive instead of spinning until the budget runs out.
+    static OUTSTANDING_TASKS: Cell<u64> = const { Cell::new(0) };
+    // Incremented every time a guarded task's top-level future is polled, whether or
+    // not that poll finishes it. Unlike `OUTSTANDING_TASKS` (which only changes on
+    // completion), this also catches a task that's actively making progress across
+    // several poll rounds — e.g. chained `yield_now().await`s or a channel hand-off
+    // between two tasks — so the budget loop doesn't mistake that for idle.
+    static TOTAL_POLLS: Cell<u64> = const { Cell::new(0) };
 }
 
 // Tasks don't need Send because they never leave the main thread
 type AsyncFnToSpawn = Pin<Box<dyn Future<Output = ()> + 'static>>;
 
+// Tasks submitted from background threads must be Send, since they cross threads
+// before landing on the main thread's `LocalSet`.
+type CrossThreadTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A `Send + Clone` handle for submitting work to the main-thread runtime from any
+/// thread. Mirrors the "spawn from other threads" pattern tokio's current-thread
+/// runtime provides through its own `Handle`: futures handed to `spawn` are enqueued
+/// on a channel and executed on the main thread the next time
+/// `swift_bridge_update_runtime` drains it.
+///
+/// Obtain one via [`get_async_runtime`]. For `!Send` futures that are already on the
+/// main thread, use [`spawn_task`] instead.
+#[derive(Clone)]
+pub struct AsyncRuntimeHandle {
+    sender: mpsc::Sender<CrossThreadTask>,
+}
+
+impl AsyncRuntimeHandle {
+    /// Enqueues a `Send` future to run on the main thread. Safe to call from any
+    /// thread, including the main thread itself.
+    pub fn spawn<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // The receiving end lives in the main thread's thread-local storage and is
+        // only dropped when that thread exits (`swift_bridge_shutdown_runtime` drains
+        // it but keeps it); a send error here would mean the app is shutting down.
+        let _ = self.sender.send(Box::pin(fut));
+    }
+}
+
+/// Returns a handle for submitting tasks to the main-thread runtime. Must be called
+/// on the main thread once at startup; the returned handle may then be cloned and
+/// moved to background threads.
+pub fn get_async_runtime() -> AsyncRuntimeHandle {
+    CROSS_THREAD_CHANNEL.with(|channel| {
+        let mut channel = channel.borrow_mut();
+        if channel.is_none() {
+            *channel = Some(mpsc::channel());
+        }
+        AsyncRuntimeHandle {
+            sender: channel.as_ref().unwrap().0.clone(),
+        }
+    })
+}
+
+/// The closure shapes `SwiftCallbackWrapper` can carry behind its opaque pointer.
+/// Tagging the payload lets the wrapper free it correctly on drop (e.g. if a task is
+/// cancelled before its completion callback ever fires) without guessing which
+/// trait object is behind the pointer.
+enum SwiftCallbackPayload {
+    /// A one-shot callback, consumed the single time it's invoked (task completion).
+    Once(Box<dyn FnOnce()>),
+    /// A reusable callback that can be invoked any number of times (the panic handler).
+    Repeatable(Box<dyn Fn(&str)>),
+}
+
 #[doc(hidden)]
+#[repr(transparent)]
 pub struct SwiftCallbackWrapper(pub *mut std::ffi::c_void);
 
+/// Wraps a Swift closure's context pointer and trampoline into a one-shot
+/// [`SwiftCallbackWrapper`] suitable for `TaskHandle::set_on_complete`. This is the
+/// only supported way to construct a wrapper that `invoke`/`Drop` can safely consume:
+/// the `swift_bridge` codegen calls this when lowering a Swift closure across the FFI
+/// boundary instead of constructing the wrapper by hand.
+///
+/// # Safety
+/// `call` must be safe to invoke exactly once with `context`, and `context` must
+/// remain valid until then.
+#[no_mangle]
+pub unsafe extern "C" fn swift_bridge_callback_once(
+    context: *mut std::ffi::c_void,
+    call: unsafe extern "C" fn(*mut std::ffi::c_void),
+) -> SwiftCallbackWrapper {
+    let payload = SwiftCallbackPayload::Once(Box::new(move || unsafe { call(context) }));
+    SwiftCallbackWrapper(Box::into_raw(Box::new(payload)) as *mut std::ffi::c_void)
+}
+
+/// Wraps a Swift closure's context pointer and trampoline into a reusable
+/// [`SwiftCallbackWrapper`] suitable for `swift_bridge_set_panic_handler`. See
+/// [`swift_bridge_callback_once`] for the one-shot equivalent and its safety
+/// contract.
+///
+/// # Safety
+/// `call` must be safe to invoke any number of times with `context` and a
+/// NUL-terminated UTF-8 message, and `context` must remain valid for the life of the
+/// returned wrapper.
+#[no_mangle]
+pub unsafe extern "C" fn swift_bridge_callback_repeatable(
+    context: *mut std::ffi::c_void,
+    call: unsafe extern "C" fn(*mut std::ffi::c_void, *const std::os::raw::c_char),
+) -> SwiftCallbackWrapper {
+    let payload = SwiftCallbackPayload::Repeatable(Box::new(move |message: &str| {
+        let c_message = std::ffi::CString::new(message).unwrap_or_default();
+        unsafe { call(context, c_message.as_ptr()) };
+    }));
+    SwiftCallbackWrapper(Box::into_raw(Box::new(payload)) as *mut std::ffi::c_void)
+}
+
+impl SwiftCallbackWrapper {
+    /// Invokes a one-shot completion callback, consuming it.
+    ///
+    /// # Safety
+    /// `self.0` must point to a `SwiftCallbackPayload::Once` created for this purpose
+    /// (as the `swift_bridge` codegen does when handing a Swift closure across the
+    /// FFI boundary), and must not have already been consumed.
+    unsafe fn invoke(self) {
+        let payload = *Box::from_raw(self.0 as *mut SwiftCallbackPayload);
+        // The allocation has already been reclaimed above; skip our own `Drop`.
+        std::mem::forget(self);
+        if let SwiftCallbackPayload::Once(f) = payload {
+            f();
+        }
+    }
+
+    /// Invokes a reusable, non-consuming callback such as the panic handler.
+    ///
+    /// # Safety
+    /// `self.0` must point to a `SwiftCallbackPayload::Repeatable` created for this
+    /// purpose.
+    unsafe fn invoke_with_message(&self, message: &str) {
+        if let SwiftCallbackPayload::Repeatable(f) = &*(self.0 as *const SwiftCallbackPayload) {
+            f(message);
+        }
+    }
+}
+
+impl Drop for SwiftCallbackWrapper {
+    /// Frees a callback that was replaced or discarded (e.g. a cancelled task's
+    /// `on_complete`) without ever being invoked.
+    fn drop(&mut self) {
+        if self.0.is_null() {
+            return;
+        }
+        // Safety: every live `SwiftCallbackWrapper` points at a `SwiftCallbackPayload`
+        // box; `invoke` forgets `self` after reclaiming it, so this only runs for
+        // wrappers that were never consumed.
+        unsafe {
+            drop(Box::from_raw(self.0 as *mut SwiftCallbackPayload));
+        }
+    }
+}
+
+/// Turns a caught panic payload into a message describing why an async task failed.
+fn panic_payload_to_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "task panicked with a non-string payload".to_string()
+    }
+}
+
+/// Delivers a runtime or task failure to the registered panic handler, if any.
+fn report_failure(message: &str) {
+    PANIC_HANDLER.with(|handler| {
+        if let Some(handler) = &*handler.borrow() {
+            // Safety: only ever set by `swift_bridge_set_panic_handler`, which
+            // documents the required `SwiftCallbackPayload::Repeatable` representation.
+            unsafe { handler.invoke_with_message(message) };
+        }
+    });
+}
+
+/// A future adapter that catches panics from polling the inner future, so that one
+/// bad task can't unwind across the FFI boundary into the Swift frame loop. Modeled
+/// on async-task's panic-propagation support.
+struct CatchUnwindFuture<F> {
+    inner: F,
+}
+
+impl<F: Future> Future for CatchUnwindFuture<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        TOTAL_POLLS.with(|count| count.set(count.get() + 1));
+        // Safety: standard pin projection; `inner` is never moved out of `self`.
+        let inner = unsafe { self.map_unchecked_mut(|s| &mut s.inner) };
+        match catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(panic_payload_to_message(payload))),
+        }
+    }
+}
+
+/// Registers a callback invoked on the main thread whenever a spawned task panics,
+/// or the runtime itself fails to initialize.
+///
+/// `handler` must be a `SwiftCallbackPayload::Repeatable` wrapper, as created by
+/// [`swift_bridge_callback_repeatable`]; a one-shot wrapper is stored but never invoked.
+#[no_mangle]
+pub extern "C" fn swift_bridge_set_panic_handler(handler: SwiftCallbackWrapper) {
+    PANIC_HANDLER.with(|cell| *cell.borrow_mut() = Some(handler));
+}
+
+/// Shared state between a spawned task and the `TaskHandle` used to observe it.
+struct TaskShared<T> {
+    result: Option<T>,
+    on_complete: Option<SwiftCallbackWrapper>,
+}
+
+/// A handle to a task spawned with [`spawn_task_with_result`].
+///
+/// Unlike `spawn_task`, the future's output isn't dropped on the floor: `poll` reports
+/// whether the task is still running or has produced a value.
+///
+/// `TaskHandle<T>` is generic, so it can't itself be a `#[no_mangle] extern "C"`
+/// export — a concrete `swift_bridge_poll_task` entry point has to be generated
+/// per-`T` by `swift_bridge`'s proc-macro codegen (not present in this file), the
+/// same way it generates the rest of the per-type FFI glue. This module only
+/// provides the generic Rust-side primitive that codegen wraps.
+pub struct TaskHandle<T> {
+    shared: Rc<RefCell<TaskShared<T>>>,
+    cancel_state: Rc<CancelState>,
+}
+
+impl<T> TaskHandle<T> {
+    /// Checks for the task's result without blocking. Returns `Ready` with the value
+    /// the first time it's called after the task resolves, taking the value out of
+    /// the handle; every other call (before resolution, after the value has been
+    /// taken, or for a cancelled task) returns `Pending`.
+    pub fn poll(&self) -> Poll<T> {
+        match self.shared.borrow_mut().result.take() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Registers a callback to be invoked on the main thread once the task resolves.
+    /// If the task has already resolved and its value hasn't been taken by `poll` yet,
+    /// the callback fires immediately instead of being stored, since the task's own
+    /// completion code already ran and won't come back to deliver it. A callback
+    /// registered after `poll` has taken the value, or on a cancelled task, is stored
+    /// and never invoked.
+    pub fn set_on_complete(&self, callback: SwiftCallbackWrapper) {
+        if self.shared.borrow().result.is_some() {
+            // Safety: constructed by the caller from a Swift closure and only ever
+            // consumed once, here.
+            unsafe { callback.invoke() };
+            return;
+        }
+        self.shared.borrow_mut().on_complete = Some(callback);
+    }
+
+    /// Cancels the task. The underlying future is dropped before its next poll
+    /// rather than being driven to completion. This wakes the task immediately (via
+    /// the waker captured on its last poll) so cancellation takes effect promptly
+    /// even if the task is currently parked mid-await on something that wouldn't
+    /// otherwise wake it again, e.g. a timer that's been cancelled elsewhere.
+    pub fn cancel(&self) {
+        self.cancel_state.cancelled.set(true);
+        if let Some(waker) = self.cancel_state.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+
+    /// Returns a [`CancellationToken`] observing the same cancellation as this
+    /// handle, for anything besides the task's own future that needs to react to
+    /// `cancel()` (e.g. a sub-task spawned from within it).
+    pub fn cancellation_token(&self) -> CancellationToken {
+        CancellationToken {
+            state: Rc::clone(&self.cancel_state),
+        }
+    }
+}
+
+/// Cancellation state shared between a `TaskHandle`, the `Cancellable` future it
+/// watches, and any `CancellationToken`s handed to the task's own future: a flag
+/// checked on every poll, plus the most recently seen waker so `TaskHandle::cancel`
+/// can force a prompt re-poll.
+struct CancelState {
+    cancelled: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+/// A cloneable flag a task's own future can poll between (or even mid-) awaits to
+/// cooperatively wind itself down, instead of only being torn down from the outside
+/// the next time `Cancellable` gets polled. `spawn_task_with_result` hands one to the
+/// closure that builds the future; `TaskHandle::cancellation_token` hands out further
+/// clones to anything else that needs to observe the same cancellation.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Rc<CancelState>,
+}
+
+impl CancellationToken {
+    /// Reports whether `TaskHandle::cancel` has been called for this task.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.get()
+    }
+}
+
+/// A future that checks a shared [`CancelState`] on every poll, dropping the wrapped
+/// future and completing with `None` the first time it finds the flag set instead of
+/// polling further. This is the `CancellationToken`-style flag `TaskHandle::cancel` sets.
+struct Cancellable<T> {
+    inner: Option<Pin<Box<dyn Future<Output = T>>>>,
+    state: Rc<CancelState>,
+}
+
+impl<T> Future for Cancellable<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.state.cancelled.get() {
+            this.inner = None;
+            return Poll::Ready(None);
+        }
+        *this.state.waker.borrow_mut() = Some(cx.waker().clone());
+        match &mut this.inner {
+            Some(fut) => fut.as_mut().poll(cx).map(Some),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Wraps a future with panic isolation and outstanding-task bookkeeping before it's
+/// handed to the `LocalSet`. Used for both same-thread tasks (`spawn_task`) and tasks
+/// submitted across threads via `AsyncRuntimeHandle`, so both get the same panic
+/// reporting and budget-loop visibility.
+fn guard_task<F>(task: F) -> AsyncFnToSpawn
+where
+    F: Future<Output = ()> + 'static,
+{
+    OUTSTANDING_TASKS.with(|count| count.set(count.get() + 1));
+    Box::pin(async move {
+        if let Err(message) = (CatchUnwindFuture { inner: task }).await {
+            report_failure(&message);
+        }
+        OUTSTANDING_TASKS.with(|count| count.set(count.get() - 1));
+    })
+}
 
 pub fn spawn_task(task: AsyncFnToSpawn) {
-    TASKS.with(|tasks| tasks.borrow_mut().push(task));
+    TASKS.with(|tasks| tasks.borrow_mut().push_back(guard_task(task)));
+}
+
+/// Like `spawn_task`, but keeps the future's output instead of discarding it. The
+/// returned `TaskHandle<T>` can be polled (without blocking) to observe completion,
+/// retrieve the value, or cancel the task outright, so Swift can consume Rust async
+/// results instead of only being able to fire-and-forget them.
+///
+/// `make_fut` is handed a [`CancellationToken`] before the future is built, so the
+/// future itself can poll `is_cancelled()` between awaits and wind down cooperatively
+/// instead of only ever being torn down from the outside by `Cancellable` dropping it
+/// on its next poll.
+pub fn spawn_task_with_result<T, F>(make_fut: impl FnOnce(CancellationToken) -> F) -> TaskHandle<T>
+where
+    T: 'static,
+    F: Future<Output = T> + 'static,
+{
+    let shared = Rc::new(RefCell::new(TaskShared {
+        result: None,
+        on_complete: None,
+    }));
+    let cancel_state = Rc::new(CancelState {
+        cancelled: Cell::new(false),
+        waker: RefCell::new(None),
+    });
+
+    let fut = make_fut(CancellationToken {
+        state: Rc::clone(&cancel_state),
+    });
+
+    let shared_for_task = Rc::clone(&shared);
+    let cancel_state_for_task = Rc::clone(&cancel_state);
+    let wrapped: AsyncFnToSpawn = Box::pin(async move {
+        let cancellable = Cancellable {
+            inner: Some(Box::pin(fut)),
+            state: cancel_state_for_task,
+        };
+        let Some(value) = cancellable.await else {
+            // Cancelled before it resolved; there's no result or callback to deliver.
+            return;
+        };
+        let callback = {
+            let mut state = shared_for_task.borrow_mut();
+            state.result = Some(value);
+            state.on_complete.take()
+        };
+        if let Some(callback) = callback {
+            // Safety: constructed by `set_on_complete` from a Swift closure and only
+            // ever consumed once, here.
+            unsafe { callback.invoke() };
+        }
+    });
+    spawn_task(wrapped);
+
+    TaskHandle { shared, cancel_state }
 }
 
 /// Updates the runtime - must be called regularly on the main thread
-/// (e.g. every frame via CADisplayLink)
+/// (e.g. every frame via CADisplayLink).
+///
+/// `max_duration_micros` bounds how long this call is allowed to keep driving ready
+/// tasks before returning control to the caller; pass `0` to run only a single
+/// cooperative step with no time budget. A burst of work or a
+/// long-running future will resume on the next call instead of hitching the frame.
 #[no_mangle]
-pub extern "C" fn swift_bridge_update_runtime() {
+pub extern "C" fn swift_bridge_update_runtime(max_duration_micros: u64) {
     ASYNC_RUNTIME.with(|runtime_cell| {
-        // Initialize runtime if needed
+        // Initialize runtime if needed. We use a current-thread runtime paired with a
+        // persistent `LocalSet` so that `!Send` futures can be spawned and make
+        // incremental progress across frames instead of being driven to completion in
+        // a single `block_on` call.
         if runtime_cell.borrow().is_none() {
-            *runtime_cell.borrow_mut() = Some(Runtime::new().unwrap());
+            match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(rt) => *runtime_cell.borrow_mut() = Some((rt, LocalSet::new())),
+                Err(err) => {
+                    report_failure(&format!("failed to initialize async runtime: {err}"));
+                    return;
+                }
+            }
         }
 
-        // Process pending tasks
-        if let Some(rt) = &*runtime_cell.borrow() {
-            rt.block_on(async {
-                while let Some(task) = TASKS.with(|t| t.borrow_mut().pop()) {
-                    task.await;
+        if let Some((rt, local)) = &mut *runtime_cell.borrow_mut() {
+            // Move any freshly queued same-thread tasks onto the local set.
+            TASKS.with(|tasks| {
+                for task in tasks.borrow_mut().drain(..) {
+                    local.spawn_local(task);
                 }
-                tokio::task::yield_now().await;
             });
+
+            // Drain tasks submitted from other threads via `AsyncRuntimeHandle`. Route
+            // them through the same `guard_task` wrapping as same-thread tasks so a
+            // panic here is caught and reported instead of being swallowed by
+            // tokio's task harness.
+            CROSS_THREAD_CHANNEL.with(|channel| {
+                if let Some((_, receiver)) = &*channel.borrow() {
+                    while let Ok(task) = receiver.try_recv() {
+                        local.spawn_local(guard_task(task));
+                    }
+                }
+            });
+
+            // Drive the local set in bounded cooperative steps, letting timers and IO
+            // make progress without blocking the main thread until everything
+            // finishes. Each step polls every ready task once (via `yield_now`); we
+            // repeat steps until either the time budget is exhausted or a step polls
+            // nothing at all, since at that point every remaining task is parked on
+            // something that won't fire on its own and looping further would just
+            // busy-spin to the deadline.
+            if max_duration_micros == 0 {
+                rt.block_on(local.run_until(tokio::task::yield_now()));
+            } else {
+                let budget = Duration::from_micros(max_duration_micros);
+                let start = Instant::now();
+                while OUTSTANDING_TASKS.with(|count| count.get()) > 0 {
+                    let polls_before = TOTAL_POLLS.with(|count| count.get());
+                    rt.block_on(local.run_until(tokio::task::yield_now()));
+                    let made_progress = TOTAL_POLLS.with(|count| count.get()) != polls_before;
+                    if !made_progress || start.elapsed() >= budget {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Tears the runtime down: cancels every outstanding same-thread and `LocalSet`
+/// task (dropping the `LocalSet` drops its unfinished tasks), flushes any
+/// not-yet-collected cross-thread submissions, and resets the runtime so the next
+/// call to `swift_bridge_update_runtime` initializes a fresh one. Call this when the
+/// owning view controller disappears.
+#[no_mangle]
+pub extern "C" fn swift_bridge_shutdown_runtime() {
+    ASYNC_RUNTIME.with(|runtime_cell| {
+        // Dropping the `LocalSet` cancels any tasks it hasn't finished running.
+        // Those tasks' `guard_task` wrapper never reaches its own decrement when
+        // dropped before finishing like this, so reset the counter below rather than
+        // leaving it permanently inflated.
+        *runtime_cell.borrow_mut() = None;
+    });
+    TASKS.with(|tasks| tasks.borrow_mut().clear());
+    CROSS_THREAD_CHANNEL.with(|channel| {
+        if let Some((_, receiver)) = &*channel.borrow() {
+            while receiver.try_recv().is_ok() {}
         }
     });
+    OUTSTANDING_TASKS.with(|count| count.set(0));
 }